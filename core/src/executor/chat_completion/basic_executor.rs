@@ -1,11 +1,14 @@
 use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Instant;
 
 use crate::model::types::ModelEvent;
 use crate::model::types::{LLMFinishEvent, ToolStartEvent};
 use crate::types::engine::Model;
-use crate::types::gateway::ChatCompletionMessage;
+use crate::types::gateway::{ChatCompletionMessage, ToolCall};
 use crate::GatewayError;
 
+use super::tool_executor::ToolExecutor;
 use crate::{
     model::ModelInstance,
     types::{
@@ -13,7 +16,8 @@ use crate::{
             ChatCompletionChoice, ChatCompletionRequest, ChatCompletionResponse,
             ChatCompletionUsage,
         },
-        threads::Message,
+        message::MessageType,
+        threads::{Message, MessageContentType},
     },
 };
 use tracing::Span;
@@ -24,21 +28,44 @@ use crate::handler::record_map_err;
 use crate::GatewayApiError;
 
 pub type FinishEventHandle =
-    tokio::task::JoinHandle<(Option<LLMFinishEvent>, Option<Vec<ToolStartEvent>>)>;
+    tokio::task::JoinHandle<(Vec<LLMFinishEvent>, Vec<ToolStartEvent>)>;
+
+/// Number of agentic tool-calling rounds allowed before `execute()` gives
+/// up and returns whatever the model last produced.
+pub const DEFAULT_MAX_STEPS: usize = 5;
+
+/// Upper bound on how many tool calls from a single turn run at once when
+/// no explicit `max_concurrent_tools` is configured.
+fn default_max_concurrent_tools() -> usize {
+    std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(4)
+}
+
+/// The durable parts of a completed turn: everything a cache needs to
+/// replay it later byte-identically to how it was first produced,
+/// instead of minting a new id/timestamp/finish_reason on replay.
+#[derive(Debug, Clone)]
+pub struct CachedChatCompletion {
+    pub id: String,
+    pub created: i64,
+    pub finish_reason: String,
+    pub message: ChatCompletionMessage,
+}
 
 #[derive(Default)]
 pub struct BasicCacheContext {
     pub events_sender: Option<tokio::sync::mpsc::Sender<Option<ModelEvent>>>,
-    pub response_sender: Option<tokio::sync::oneshot::Sender<ChatCompletionMessage>>,
+    pub response_sender: Option<tokio::sync::oneshot::Sender<CachedChatCompletion>>,
     pub cached_events: Option<Vec<ModelEvent>>,
-    pub cached_response: Option<ChatCompletionMessage>,
+    pub cached_response: Option<CachedChatCompletion>,
 }
 
 #[allow(clippy::too_many_arguments)]
 pub async fn execute(
     request: ChatCompletionRequest,
     model: Box<dyn ModelInstance>,
-    messages: Vec<Message>,
+    mut messages: Vec<Message>,
     tags: HashMap<String, String>,
     tx: tokio::sync::mpsc::Sender<Option<ModelEvent>>,
     span: Span,
@@ -46,8 +73,18 @@ pub async fn execute(
     input_vars: HashMap<String, serde_json::Value>,
     cache_context: BasicCacheContext,
     model_metadata: Option<Model>,
+    tool_executor: Option<Arc<dyn ToolExecutor>>,
+    max_steps: Option<usize>,
+    max_concurrent_tools: Option<usize>,
+    // Lets `execute_stream` pick the id/created up front and have this
+    // call -- and whatever it persists to the cache -- agree with it, so
+    // a later replay matches the original live stream byte-for-byte.
+    turn_id: Option<(String, i64)>,
 ) -> Result<ChatCompletionResponse, GatewayApiError> {
+    let (completion_id, created) =
+        turn_id.unwrap_or_else(|| (Uuid::new_v4().to_string(), chrono::Utc::now().timestamp()));
     let (inner_tx, mut rx) = tokio::sync::mpsc::channel::<Option<ModelEvent>>(10000);
+    let response_sender = cache_context.response_sender;
     tokio::spawn(async move {
         while let Some(event) = rx.recv().await {
             if let Some(sender) = &cache_context.events_sender {
@@ -57,63 +94,163 @@ pub async fn execute(
         }
     });
 
-    let response = model
-        .invoke(input_vars.clone(), inner_tx, messages.clone(), tags.clone())
-        .instrument(span.clone())
-        .await
-        .map_err(|e| record_map_err(e, span.clone()))?;
+    let max_steps = max_steps.unwrap_or(DEFAULT_MAX_STEPS);
+    let tool_semaphore = Arc::new(tokio::sync::Semaphore::new(
+        max_concurrent_tools.unwrap_or_else(default_max_concurrent_tools),
+    ));
+    let thread_id = messages.first().and_then(|m| m.thread_id.clone());
+    let user_id = messages.first().map(|m| m.user_id.clone()).unwrap_or_default();
 
-    if let Some(response_sender) = cache_context.response_sender {
-        response_sender.send(response.message().clone()).unwrap();
-    }
+    let mut last_tool_calls: Option<Vec<ToolCall>> = None;
+    let mut step = 0usize;
+
+    let (final_message, finish_reason) = loop {
+        step += 1;
+
+        let response = model
+            .invoke(input_vars.clone(), inner_tx.clone(), messages.clone(), tags.clone())
+            .instrument(span.clone())
+            .await
+            .map_err(|e| record_map_err(e, span.clone()))?;
+
+        let message = response.message().clone();
+
+        let Some(tool_calls) = message.tool_calls.clone() else {
+            let content = message.content.as_ref().map(|c| c.as_string()).ok_or_else(|| {
+                GatewayApiError::GatewayError(GatewayError::CustomError(
+                    "No content in response".to_string(),
+                ))
+            })?;
+            span.record("response", content);
+            break (message, response.finish_reason().to_string());
+        };
+
+        let calls_str = serde_json::to_string(&tool_calls).unwrap();
+        span.record("response", calls_str);
 
-    let finish_reason = match (&response.message().tool_calls, &response.message().content) {
-        (Some(_), _) => {
-            let calls = serde_json::to_string(&response.message().tool_calls).unwrap();
-            span.record("response", calls);
-            Ok("tool_calls".to_string())
+        let Some(executor) = tool_executor.as_ref() else {
+            // No executor wired in: hand control back to the caller, as before.
+            break (message, "tool_calls".to_string());
+        };
+
+        if step >= max_steps {
+            break (message, "max_steps".to_string());
+        }
+
+        // Same calls as last round means the model is stuck in a loop and
+        // won't converge on its own; stop rather than spin forever.
+        if last_tool_calls.as_ref() == Some(&tool_calls) {
+            break (message, "max_steps".to_string());
+        }
+        last_tool_calls = Some(tool_calls.clone());
+
+        messages.push(Message {
+            model_name: request.model.clone(),
+            thread_id: thread_id.clone(),
+            user_id: user_id.clone(),
+            content_type: MessageContentType::Text,
+            content: message.content.as_ref().map(|c| c.as_string()),
+            content_array: vec![],
+            r#type: MessageType::AI,
+            tool_call_id: None,
+            tool_calls: Some(tool_calls.clone()),
+        });
+
+        let mut tasks = Vec::with_capacity(tool_calls.len());
+        for (index, tool_call) in tool_calls.iter().cloned().enumerate() {
+            let executor = executor.clone();
+            let inner_tx = inner_tx.clone();
+            let tags = tags.clone();
+            let semaphore = tool_semaphore.clone();
+            let model_name = request.model.clone();
+            let thread_id = thread_id.clone();
+            let user_id = user_id.clone();
+            let tool_call_id = tool_call.id.clone();
+            let task = tokio::spawn(async move {
+                let _permit = semaphore.acquire_owned().await.unwrap();
+                run_tool_call(tool_call, executor, inner_tx, tags, model_name, thread_id, user_id)
+                    .await
+            });
+            tasks.push((index, tool_call_id, task));
         }
-        (None, Some(c)) => {
-            span.record("response", c.as_string());
-            Ok(response.finish_reason().to_string())
+
+        let mut results = Vec::with_capacity(tasks.len());
+        for (index, tool_call_id, task) in tasks {
+            // A panicking tool task (e.g. a panic inside the executor) must
+            // not abort the whole turn; surface it as the tool's own error reply.
+            let message = match task.await {
+                Ok(message) => message,
+                Err(e) => error_tool_message(
+                    tool_call_id,
+                    format!("tool call task panicked: {e}"),
+                    request.model.clone(),
+                    thread_id.clone(),
+                    user_id.clone(),
+                ),
+            };
+            results.push((index, message));
         }
-        _ => Err(GatewayApiError::GatewayError(GatewayError::CustomError(
-            "No content in response".to_string(),
-        ))),
-    }?;
+        // Execution overlaps, but history order must stay deterministic.
+        results.sort_by_key(|(index, _)| *index);
+        messages.extend(results.into_iter().map(|(_, message)| message));
+    };
+
+    // Every round only ever borrowed a clone of `inner_tx`; drop the
+    // original so the forwarder task's `rx.recv()` loop ends and the
+    // `handle` finish-collector below isn't waiting on a channel that
+    // never closes.
+    drop(inner_tx);
+
+    if let Some(response_sender) = response_sender {
+        response_sender
+            .send(CachedChatCompletion {
+                id: completion_id.clone(),
+                created,
+                finish_reason: finish_reason.clone(),
+                message: final_message.clone(),
+            })
+            .unwrap();
+    }
 
-    let (u, _) = if let Some(handle) = handle {
+    // The accumulation below sums every `LLMFinishEvent` that `handle`
+    // yields; the reported usage covers every round only when the handle
+    // yields one finish event per round.
+    let (finishes, _tool_starts) = if let Some(handle) = handle {
         handle.await.unwrap()
     } else {
-        (None, None)
-    };
-    let model_usage = u.and_then(|u| u.usage);
-    let is_cache_used = model_usage.as_ref().map(|u| u.is_cache_used);
-    let usage: ChatCompletionUsage = match model_usage {
-        Some(u) => ChatCompletionUsage {
-            prompt_tokens: u.input_tokens as i32,
-            completion_tokens: u.output_tokens as i32,
-            total_tokens: u.total_tokens as i32,
-            prompt_tokens_details: u.prompt_tokens_details.clone(),
-            completion_tokens_details: u.completion_tokens_details.clone(),
-            cost: 0.0,
-        },
-        None => ChatCompletionUsage {
-            ..Default::default()
-        },
+        (vec![], vec![])
     };
 
+    let mut usage = ChatCompletionUsage::default();
+    let mut is_cache_used = None;
+    for finish in finishes {
+        if let Some(model_usage) = finish.usage {
+            usage.prompt_tokens += model_usage.input_tokens as i32;
+            usage.completion_tokens += model_usage.output_tokens as i32;
+            usage.total_tokens += model_usage.total_tokens as i32;
+            // Keep the first round's breakdown rather than overwriting it
+            // with each subsequent round's, which would silently drop detail.
+            if usage.prompt_tokens_details.is_none() {
+                usage.prompt_tokens_details = model_usage.prompt_tokens_details.clone();
+            }
+            if usage.completion_tokens_details.is_none() {
+                usage.completion_tokens_details = model_usage.completion_tokens_details.clone();
+            }
+            is_cache_used = is_cache_used.or(Some(model_usage.is_cache_used));
+        }
+    }
+
     let response = ChatCompletionResponse {
-        id: Uuid::new_v4().to_string(),
+        id: completion_id,
         object: "chat.completion".to_string(),
-        created: chrono::Utc::now().timestamp(),
+        created,
         model: model_metadata.map_or(request.model.clone(), |m| {
             format!("{}/{}", m.provider_name, m.name)
         }),
         choices: vec![ChatCompletionChoice {
             index: 0,
-            message: response.message().clone(),
-            finish_reason: Some(finish_reason.clone()),
+            message: final_message,
+            finish_reason: Some(finish_reason),
         }],
         usage,
         is_cache_used,
@@ -121,3 +258,85 @@ pub async fn execute(
 
     Ok(response)
 }
+
+/// Runs a single [`ToolCall`] and turns its outcome into a `Tool` message,
+/// emitting a `ToolStart` event up front and a per-call tracing span so
+/// concurrent calls still get individual latency and outcome records.
+async fn run_tool_call(
+    tool_call: ToolCall,
+    executor: Arc<dyn ToolExecutor>,
+    inner_tx: tokio::sync::mpsc::Sender<Option<ModelEvent>>,
+    tags: HashMap<String, String>,
+    model_name: String,
+    thread_id: Option<String>,
+    user_id: String,
+) -> Message {
+    // The event receiver may already be gone (e.g. the caller dropped the
+    // stream); that's not this tool call's problem, so don't let it panic.
+    let _ = inner_tx
+        .send(Some(ModelEvent::ToolStart(ToolStartEvent::new(
+            tool_call.id.clone(),
+            tool_call.function.name.clone(),
+            tool_call.function.arguments.clone(),
+        ))))
+        .await;
+
+    let tool_span = tracing::info_span!(
+        "tool_call",
+        name = %tool_call.function.name,
+        tool_call_id = %tool_call.id,
+        latency_ms = tracing::field::Empty,
+        outcome = tracing::field::Empty,
+    );
+
+    let started_at = Instant::now();
+    let result = executor
+        .execute(&tool_call, &tags)
+        .instrument(tool_span.clone())
+        .await;
+    let latency_ms = started_at.elapsed().as_millis() as i64;
+
+    let (content, outcome) = match result {
+        Ok(value) => (serde_json::to_string(&value).unwrap_or_default(), "ok"),
+        Err(e) => (
+            serde_json::json!({ "error": e.to_string() }).to_string(),
+            "error",
+        ),
+    };
+    tool_span.record("latency_ms", latency_ms);
+    tool_span.record("outcome", outcome);
+
+    Message {
+        model_name,
+        thread_id,
+        user_id,
+        content_type: MessageContentType::Text,
+        content: Some(content),
+        content_array: vec![],
+        r#type: MessageType::Tool,
+        tool_call_id: Some(tool_call.id.clone()),
+        tool_calls: None,
+    }
+}
+
+/// Builds a `Tool` message carrying a structured error payload, for tool
+/// calls that never produced a normal result (e.g. their task panicked).
+fn error_tool_message(
+    tool_call_id: String,
+    error: String,
+    model_name: String,
+    thread_id: Option<String>,
+    user_id: String,
+) -> Message {
+    Message {
+        model_name,
+        thread_id,
+        user_id,
+        content_type: MessageContentType::Text,
+        content: Some(serde_json::json!({ "error": error }).to_string()),
+        content_array: vec![],
+        r#type: MessageType::Tool,
+        tool_call_id: Some(tool_call_id),
+        tool_calls: None,
+    }
+}
@@ -0,0 +1,3 @@
+pub mod basic_executor;
+pub mod stream;
+pub mod tool_executor;
@@ -0,0 +1,262 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use futures::Stream;
+use serde::Serialize;
+use tokio_stream::wrappers::ReceiverStream;
+use tracing::Span;
+use uuid::Uuid;
+
+use super::basic_executor::{execute, BasicCacheContext, CachedChatCompletion, FinishEventHandle};
+use super::tool_executor::ToolExecutor;
+use crate::model::types::ModelEvent;
+use crate::model::ModelInstance;
+use crate::types::engine::Model;
+use crate::types::gateway::{ChatCompletionRequest, ChatCompletionUsage, ToolCall};
+use crate::types::threads::Message;
+
+/// Incremental delta carried by a single [`ChatCompletionChunk`] choice,
+/// mirroring the OpenAI-compatible streaming shape.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ChatCompletionMessageDelta {
+    pub role: Option<String>,
+    pub content: Option<String>,
+    pub tool_calls: Option<Vec<ToolCall>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ChatCompletionChunkChoice {
+    pub index: i32,
+    pub delta: ChatCompletionMessageDelta,
+    pub finish_reason: Option<String>,
+}
+
+/// One frame of a streamed chat completion. Every chunk but the last
+/// carries a content or tool-call delta; the last carries no delta but
+/// the aggregated [`ChatCompletionUsage`] and `finish_reason` for the
+/// whole turn.
+#[derive(Debug, Clone, Serialize)]
+pub struct ChatCompletionChunk {
+    pub id: String,
+    pub object: String,
+    pub created: i64,
+    pub model: String,
+    pub choices: Vec<ChatCompletionChunkChoice>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub usage: Option<ChatCompletionUsage>,
+}
+
+impl ChatCompletionChunk {
+    fn delta(id: &str, created: i64, model: &str, delta: ChatCompletionMessageDelta) -> Self {
+        Self {
+            id: id.to_string(),
+            object: "chat.completion.chunk".to_string(),
+            created,
+            model: model.to_string(),
+            choices: vec![ChatCompletionChunkChoice {
+                index: 0,
+                delta,
+                finish_reason: None,
+            }],
+            usage: None,
+        }
+    }
+
+    fn terminal(
+        id: &str,
+        created: i64,
+        model: &str,
+        finish_reason: String,
+        usage: ChatCompletionUsage,
+    ) -> Self {
+        Self {
+            id: id.to_string(),
+            object: "chat.completion.chunk".to_string(),
+            created,
+            model: model.to_string(),
+            choices: vec![ChatCompletionChunkChoice {
+                index: 0,
+                delta: ChatCompletionMessageDelta::default(),
+                finish_reason: Some(finish_reason),
+            }],
+            usage: Some(usage),
+        }
+    }
+}
+
+/// Converts a raw [`ModelEvent`] into the delta chunk it represents, if
+/// any. Events with no user-visible delta (tool starts, finish events)
+/// are dropped here; they still reach `cache_context.events_sender` via
+/// `execute()` itself so a later replay can reconstruct usage. `id` and
+/// `created` are fixed for the whole turn, live or replayed, so every
+/// chunk in a turn carries the same pair.
+fn event_to_chunk(id: &str, created: i64, model: &str, event: &ModelEvent) -> Option<ChatCompletionChunk> {
+    match event {
+        ModelEvent::ContentDelta(delta) => Some(ChatCompletionChunk::delta(
+            id,
+            created,
+            model,
+            ChatCompletionMessageDelta {
+                role: None,
+                content: Some(delta.content.clone()),
+                tool_calls: None,
+            },
+        )),
+        ModelEvent::ToolCallDelta(delta) => Some(ChatCompletionChunk::delta(
+            id,
+            created,
+            model,
+            ChatCompletionMessageDelta {
+                role: None,
+                content: None,
+                tool_calls: Some(delta.tool_calls.clone()),
+            },
+        )),
+        _ => None,
+    }
+}
+
+fn aggregate_usage_from_events(events: &[ModelEvent]) -> ChatCompletionUsage {
+    let mut usage = ChatCompletionUsage::default();
+    for event in events {
+        if let ModelEvent::LLMFinish(finish) = event {
+            if let Some(model_usage) = &finish.usage {
+                usage.prompt_tokens += model_usage.input_tokens as i32;
+                usage.completion_tokens += model_usage.output_tokens as i32;
+                usage.total_tokens += model_usage.total_tokens as i32;
+                // Keep the first round's breakdown rather than overwriting it
+                // with each subsequent round's, mirroring the live path in
+                // `basic_executor::execute` so a replay's terminal frame
+                // matches the one originally streamed live.
+                if usage.prompt_tokens_details.is_none() {
+                    usage.prompt_tokens_details = model_usage.prompt_tokens_details.clone();
+                }
+                if usage.completion_tokens_details.is_none() {
+                    usage.completion_tokens_details = model_usage.completion_tokens_details.clone();
+                }
+            }
+        }
+    }
+    usage
+}
+
+/// Runs [`execute`] and exposes its output as a `futures::Stream` of
+/// [`ChatCompletionChunk`]s instead of a raw `mpsc` channel, so callers
+/// can `.map`/`.forward` it straight into an SSE response. Every event
+/// is still teed into `cache_context` (by `execute()` itself) when one
+/// is configured.
+#[allow(clippy::too_many_arguments)]
+pub fn execute_stream(
+    request: ChatCompletionRequest,
+    model: Box<dyn ModelInstance>,
+    messages: Vec<Message>,
+    tags: HashMap<String, String>,
+    span: Span,
+    handle: Option<FinishEventHandle>,
+    input_vars: HashMap<String, serde_json::Value>,
+    cache_context: BasicCacheContext,
+    model_metadata: Option<Model>,
+    tool_executor: Option<Arc<dyn ToolExecutor>>,
+    max_steps: Option<usize>,
+    max_concurrent_tools: Option<usize>,
+) -> impl Stream<Item = ChatCompletionChunk> {
+    let (event_tx, mut event_rx) = tokio::sync::mpsc::channel::<Option<ModelEvent>>(10000);
+    let (chunk_tx, chunk_rx) = tokio::sync::mpsc::channel::<ChatCompletionChunk>(10000);
+
+    // Chosen once, up front, and passed into `execute()` so the id/created
+    // this stream's chunks carry are exactly what gets persisted to the
+    // cache -- a later `replay_stream` needs them to match byte-for-byte.
+    let id = Uuid::new_v4().to_string();
+    let created = chrono::Utc::now().timestamp();
+    let model_name = request.model.clone();
+
+    let forward_id = id.clone();
+    let forward_model = model_name.clone();
+    let forward_chunk_tx = chunk_tx.clone();
+    let forward_handle = tokio::spawn(async move {
+        while let Some(Some(event)) = event_rx.recv().await {
+            if let Some(chunk) = event_to_chunk(&forward_id, created, &forward_model, &event) {
+                if forward_chunk_tx.send(chunk).await.is_err() {
+                    break;
+                }
+            }
+        }
+    });
+
+    let turn_id = (id.clone(), created);
+    tokio::spawn(async move {
+        let result = execute(
+            request,
+            model,
+            messages,
+            tags,
+            event_tx,
+            span,
+            handle,
+            input_vars,
+            cache_context,
+            model_metadata,
+            tool_executor,
+            max_steps,
+            max_concurrent_tools,
+            Some(turn_id),
+        )
+        .await;
+
+        // `execute()` returning only means it dropped its own sender; the
+        // delta forwarder above can still have buffered events in flight.
+        // Wait for it to drain (it ends once `event_rx` closes) so the
+        // terminal frame can't overtake still-pending deltas on `chunk_rx`.
+        let _ = forward_handle.await;
+
+        let terminal = match result {
+            Ok(response) => {
+                let finish_reason = response
+                    .choices
+                    .first()
+                    .and_then(|choice| choice.finish_reason.clone())
+                    .unwrap_or_else(|| "stop".to_string());
+                ChatCompletionChunk::terminal(&id, created, &model_name, finish_reason, response.usage)
+            }
+            Err(_) => ChatCompletionChunk::terminal(
+                &id,
+                created,
+                &model_name,
+                "error".to_string(),
+                ChatCompletionUsage::default(),
+            ),
+        };
+
+        let _ = chunk_tx.send(terminal).await;
+    });
+
+    ReceiverStream::new(chunk_rx)
+}
+
+/// Rebuilds the exact chunk sequence a live [`execute_stream`] call for
+/// this turn produced, from a cache hit's recorded events and the
+/// [`CachedChatCompletion`] `execute()` persisted for it -- reusing its
+/// id/created/finish_reason rather than regenerating or guessing them,
+/// so a cached replay is byte-identical to the original live stream.
+pub fn replay_stream(
+    model: String,
+    cached_events: Vec<ModelEvent>,
+    cached_response: CachedChatCompletion,
+) -> impl Stream<Item = ChatCompletionChunk> {
+    let CachedChatCompletion { id, created, finish_reason, .. } = cached_response;
+    let usage = aggregate_usage_from_events(&cached_events);
+
+    let chunks: Vec<ChatCompletionChunk> = cached_events
+        .iter()
+        .filter_map(|event| event_to_chunk(&id, created, &model, event))
+        .chain(std::iter::once(ChatCompletionChunk::terminal(
+            &id,
+            created,
+            &model,
+            finish_reason,
+            usage,
+        )))
+        .collect();
+
+    tokio_stream::iter(chunks)
+}
@@ -0,0 +1,22 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use serde_json::Value;
+
+use crate::types::gateway::ToolCall;
+use crate::GatewayApiError;
+
+/// Resolves a single [`ToolCall`] to its executed result.
+///
+/// Implementations may run the tool in-process or hand it off to an
+/// external provider; the multi-step loop in `basic_executor::execute`
+/// only depends on this trait, so callers are free to mix both kinds of
+/// tools behind a single executor.
+#[async_trait]
+pub trait ToolExecutor: Send + Sync {
+    async fn execute(
+        &self,
+        tool_call: &ToolCall,
+        tags: &HashMap<String, String>,
+    ) -> Result<Value, GatewayApiError>;
+}
@@ -0,0 +1,85 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use serde_json::Value;
+
+use crate::executor::chat_completion::tool_executor::ToolExecutor;
+use crate::tools::transport::StdioTransport;
+use crate::types::gateway::ToolCall;
+use crate::GatewayError;
+use crate::GatewayApiError;
+
+/// A tool whose handler runs in this process, as opposed to one served by a
+/// subprocess provider over [`StdioTransport`].
+#[async_trait]
+pub trait InProcessTool: Send + Sync {
+    async fn call(&self, arguments: Value) -> Result<Value, GatewayApiError>;
+}
+
+/// Resolves each [`ToolCall`] by name to either an in-process handler or a
+/// subprocess provider, so the multi-step loop in `basic_executor::execute`
+/// can treat both kinds of tools identically.
+#[derive(Default)]
+pub struct RoutingToolExecutor {
+    in_process: HashMap<String, Arc<dyn InProcessTool>>,
+    providers: HashMap<String, Arc<StdioTransport>>,
+}
+
+impl RoutingToolExecutor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register_in_process(&mut self, name: impl Into<String>, tool: Arc<dyn InProcessTool>) {
+        self.in_process.insert(name.into(), tool);
+    }
+
+    /// Registers every tool a provider advertised via `initialize()` to
+    /// route through the same subprocess transport.
+    pub fn register_provider(&mut self, tool_names: impl IntoIterator<Item = String>, transport: Arc<StdioTransport>) {
+        for name in tool_names {
+            self.providers.insert(name, transport.clone());
+        }
+    }
+}
+
+#[async_trait]
+impl ToolExecutor for RoutingToolExecutor {
+    async fn execute(
+        &self,
+        tool_call: &ToolCall,
+        _tags: &HashMap<String, String>,
+    ) -> Result<Value, GatewayApiError> {
+        let name = tool_call.function.name.as_str();
+        let arguments = parse_arguments(&tool_call.function.arguments)?;
+
+        if let Some(tool) = self.in_process.get(name) {
+            return tool.call(arguments).await;
+        }
+
+        if let Some(transport) = self.providers.get(name) {
+            if transport.is_dead() {
+                return Err(GatewayApiError::GatewayError(GatewayError::CustomError(format!(
+                    "tool provider for `{name}` is unavailable"
+                ))));
+            }
+            return transport.call_tool(name, arguments).await;
+        }
+
+        Err(GatewayApiError::GatewayError(GatewayError::CustomError(format!(
+            "no tool registered for `{name}`"
+        ))))
+    }
+}
+
+fn parse_arguments(raw: &str) -> Result<Value, GatewayApiError> {
+    if raw.trim().is_empty() {
+        return Ok(Value::Object(Default::default()));
+    }
+    serde_json::from_str(raw).map_err(|e| {
+        GatewayApiError::GatewayError(GatewayError::CustomError(format!(
+            "invalid tool call arguments: {e}"
+        )))
+    })
+}
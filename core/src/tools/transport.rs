@@ -0,0 +1,235 @@
+use std::collections::HashMap;
+use std::process::Stdio;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStdin};
+use tokio::sync::{mpsc, oneshot, Mutex};
+
+use crate::GatewayError;
+use crate::GatewayApiError;
+
+#[derive(Debug, Serialize)]
+struct JsonRpcRequest<'a> {
+    jsonrpc: &'static str,
+    id: u64,
+    method: &'a str,
+    params: Value,
+}
+
+#[derive(Debug, Deserialize)]
+struct JsonRpcError {
+    code: i64,
+    message: String,
+}
+
+/// Synthetic error code the read loop assigns in-flight calls when the
+/// provider's stdout closes, distinguishing a dead transport from an
+/// ordinary tool-level error reply.
+const PROCESS_EXITED_CODE: i64 = -32000;
+
+#[derive(Debug, Deserialize)]
+struct JsonRpcMessage {
+    id: Option<u64>,
+    #[serde(default)]
+    result: Option<Value>,
+    #[serde(default)]
+    error: Option<JsonRpcError>,
+    #[serde(default)]
+    params: Option<Value>,
+}
+
+/// A single tool schema advertised by a provider during `initialize`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProviderToolSchema {
+    pub name: String,
+    pub description: Option<String>,
+    pub parameters: Value,
+}
+
+impl ProviderToolSchema {
+    /// Renders this schema as an OpenAI-style tool definition, ready to be
+    /// appended to a `ChatCompletionRequest`'s tool list.
+    pub fn into_tool_definition(self) -> Value {
+        serde_json::json!({
+            "type": "function",
+            "function": {
+                "name": self.name,
+                "description": self.description,
+                "parameters": self.parameters,
+            }
+        })
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct InitializeResult {
+    pub tools: Vec<ProviderToolSchema>,
+}
+
+type PendingCalls = Arc<Mutex<HashMap<u64, oneshot::Sender<Result<Value, JsonRpcError>>>>>;
+
+/// Newline-delimited JSON-RPC client for a tool-provider subprocess: writes
+/// requests to its stdin and correlates replies read off its stdout by a
+/// monotonically increasing request `id`. Unsolicited messages (no `id`)
+/// are forwarded on `events` so providers can stream progress back.
+pub struct StdioTransport {
+    child: Mutex<Child>,
+    stdin: Mutex<ChildStdin>,
+    next_id: AtomicU64,
+    pending: PendingCalls,
+    dead: AtomicBool,
+}
+
+impl StdioTransport {
+    pub fn spawn(
+        program: &str,
+        args: &[String],
+        events: mpsc::Sender<Value>,
+    ) -> Result<Self, GatewayApiError> {
+        let mut child = tokio::process::Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| {
+                GatewayApiError::GatewayError(GatewayError::CustomError(format!(
+                    "failed to spawn tool provider `{program}`: {e}"
+                )))
+            })?;
+
+        let stdin = child.stdin.take().expect("provider spawned with piped stdin");
+        let stdout = child.stdout.take().expect("provider spawned with piped stdout");
+
+        let pending: PendingCalls = Arc::new(Mutex::new(HashMap::new()));
+        tokio::spawn(Self::read_loop(stdout, pending.clone(), events));
+
+        Ok(Self {
+            child: Mutex::new(child),
+            stdin: Mutex::new(stdin),
+            next_id: AtomicU64::new(1),
+            pending,
+            dead: AtomicBool::new(false),
+        })
+    }
+
+    async fn read_loop(
+        stdout: tokio::process::ChildStdout,
+        pending: PendingCalls,
+        events: mpsc::Sender<Value>,
+    ) {
+        let mut lines = BufReader::new(stdout).lines();
+        loop {
+            match lines.next_line().await {
+                Ok(Some(line)) => {
+                    let Ok(message) = serde_json::from_str::<JsonRpcMessage>(&line) else {
+                        continue;
+                    };
+                    match message.id {
+                        Some(id) => {
+                            if let Some(sender) = pending.lock().await.remove(&id) {
+                                let outcome = match message.error {
+                                    Some(err) => Err(err),
+                                    None => Ok(message.result.unwrap_or(Value::Null)),
+                                };
+                                let _ = sender.send(outcome);
+                            }
+                        }
+                        None => {
+                            let _ = events.send(message.params.unwrap_or(Value::Null)).await;
+                        }
+                    }
+                }
+                _ => break,
+            }
+        }
+
+        // The provider's stdout is gone; nothing still waiting will ever
+        // hear back, so fail every in-flight call instead of hanging.
+        for (_, sender) in pending.lock().await.drain() {
+            let _ = sender.send(Err(JsonRpcError {
+                code: PROCESS_EXITED_CODE,
+                message: "tool provider process exited".to_string(),
+            }));
+        }
+    }
+
+    async fn call(&self, method: &str, params: Value) -> Result<Value, GatewayApiError> {
+        if self.dead.load(Ordering::Acquire) {
+            return Err(provider_unavailable());
+        }
+
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (tx, rx) = oneshot::channel();
+        self.pending.lock().await.insert(id, tx);
+
+        let mut payload = serde_json::to_vec(&JsonRpcRequest {
+            jsonrpc: "2.0",
+            id,
+            method,
+            params,
+        })
+        .unwrap();
+        payload.push(b'\n');
+
+        if let Err(e) = self.stdin.lock().await.write_all(&payload).await {
+            self.pending.lock().await.remove(&id);
+            self.dead.store(true, Ordering::Release);
+            return Err(GatewayApiError::GatewayError(GatewayError::CustomError(format!(
+                "tool provider write failed: {e}"
+            ))));
+        }
+
+        match rx.await {
+            Ok(Ok(value)) => Ok(value),
+            // An ordinary JSON-RPC error (bad arguments, the tool itself
+            // failed) is still a reply from a live provider -- only the
+            // read loop's "process exited" sentinel means the transport
+            // itself is gone.
+            Ok(Err(err)) if err.code == PROCESS_EXITED_CODE => {
+                self.dead.store(true, Ordering::Release);
+                Err(provider_unavailable())
+            }
+            Ok(Err(err)) => Err(GatewayApiError::GatewayError(GatewayError::CustomError(format!(
+                "tool provider error {}: {}",
+                err.code, err.message
+            )))),
+            Err(_) => {
+                self.dead.store(true, Ordering::Release);
+                Err(provider_unavailable())
+            }
+        }
+    }
+
+    pub async fn initialize(&self) -> Result<InitializeResult, GatewayApiError> {
+        let value = self.call("initialize", Value::Null).await?;
+        serde_json::from_value(value).map_err(|e| {
+            GatewayApiError::GatewayError(GatewayError::CustomError(format!(
+                "invalid initialize response from tool provider: {e}"
+            )))
+        })
+    }
+
+    pub async fn call_tool(&self, name: &str, arguments: Value) -> Result<Value, GatewayApiError> {
+        self.call(
+            "call_tool",
+            serde_json::json!({ "name": name, "arguments": arguments }),
+        )
+        .await
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.dead.load(Ordering::Acquire)
+    }
+}
+
+fn provider_unavailable() -> GatewayApiError {
+    GatewayApiError::GatewayError(GatewayError::CustomError(
+        "tool provider unavailable".to_string(),
+    ))
+}